@@ -0,0 +1,47 @@
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Something an input source wants the `Engine` to act on.
+///
+/// Each variant is produced by an independent task (a ticker, the window
+/// watcher, the ctrl-c handler, ...) and consumed by the single engine loop,
+/// so new input sources can be added without touching the others.
+#[derive(Debug)]
+pub enum Event {
+    /// Time to flush the current caption window to the output file.
+    CaptionTick,
+    /// Time to check whether Live Captions is still alive.
+    WindowCheck,
+    /// A new caption text pushed by the real-time UI Automation handler.
+    CaptionChanged(String),
+    /// Save what we have and stop the engine.
+    Shutdown,
+}
+
+/// The sending half of the event bus. Cheap to clone; hand one clone to each
+/// input task.
+#[derive(Clone)]
+pub struct Writer(UnboundedSender<Event>);
+
+/// The receiving half, owned by the engine loop.
+pub struct Reader(UnboundedReceiver<Event>);
+
+/// Create a connected [`Writer`]/[`Reader`] pair.
+pub fn channel() -> (Writer, Reader) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (Writer(tx), Reader(rx))
+}
+
+impl Writer {
+    /// Push an event onto the bus. A send failure only means the engine has
+    /// already stopped and is dropping its reader, so it is safe to ignore.
+    pub fn send(&self, event: Event) {
+        let _ = self.0.send(event);
+    }
+}
+
+impl Reader {
+    /// Await the next event, or `None` once every `Writer` has been dropped.
+    pub async fn recv(&mut self) -> Option<Event> {
+        self.0.recv().await
+    }
+}
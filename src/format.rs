@@ -0,0 +1,134 @@
+use chrono::{DateTime, Duration, Local};
+use clap::ValueEnum;
+
+/// One finalized caption block, ready to be written in the selected format.
+pub struct Segment {
+    /// The new caption text, one caption line per `\n`, with a trailing `\n`.
+    pub text: String,
+    /// Wall-clock time the segment was finalized.
+    pub ts: DateTime<Local>,
+    /// Offset from program start to the previous flush (program start for the
+    /// first segment). The moment the text itself first appeared on screen is
+    /// not recorded.
+    pub start_offset: Duration,
+    /// Offset from program start to when the segment was finalized.
+    pub end_offset: Duration,
+}
+
+/// Renders a [`Segment`] into the bytes appended to the output file. Kept as a
+/// trait so new formats only have to implement one method.
+pub trait Formatter {
+    fn format(&mut self, segment: &Segment) -> String;
+}
+
+/// The output formats exposed through `--format`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Jsonl,
+    Srt,
+    Vtt,
+}
+
+impl OutputFormat {
+    /// Build the matching [`Formatter`].
+    pub fn formatter(self) -> Box<dyn Formatter> {
+        match self {
+            OutputFormat::Text => Box::new(TextFormatter),
+            OutputFormat::Jsonl => Box::new(JsonlFormatter),
+            OutputFormat::Srt => Box::new(SrtFormatter { index: 0 }),
+            OutputFormat::Vtt => Box::new(VttFormatter { index: 0 }),
+        }
+    }
+}
+
+/// The original flat log: a timestamp header followed by the new lines.
+struct TextFormatter;
+
+impl Formatter for TextFormatter {
+    fn format(&mut self, segment: &Segment) -> String {
+        format!("{}\n{}", segment.ts.format("[%Y-%m-%d][%H:%M:%S]"), segment.text)
+    }
+}
+
+/// One JSON object per flushed segment.
+struct JsonlFormatter;
+
+impl Formatter for JsonlFormatter {
+    fn format(&mut self, segment: &Segment) -> String {
+        format!(
+            "{{\"ts\":\"{}\",\"text\":\"{}\"}}\n",
+            segment.ts.to_rfc3339(),
+            json_escape(segment.text.trim_end_matches('\n')),
+        )
+    }
+}
+
+/// SubRip cues, numbered, with `,mmm` millisecond separators.
+struct SrtFormatter {
+    index: u32,
+}
+
+impl Formatter for SrtFormatter {
+    fn format(&mut self, segment: &Segment) -> String {
+        self.index += 1;
+        format!(
+            "{}\n{} --> {}\n{}\n",
+            self.index,
+            srt_timestamp(segment.start_offset),
+            srt_timestamp(segment.end_offset),
+            segment.text,
+        )
+    }
+}
+
+/// WebVTT cues, with a `WEBVTT` header emitted before the first cue and `.mmm`
+/// millisecond separators.
+struct VttFormatter {
+    index: u32,
+}
+
+impl Formatter for VttFormatter {
+    fn format(&mut self, segment: &Segment) -> String {
+        self.index += 1;
+        let header = if self.index == 1 { "WEBVTT\n\n" } else { "" };
+        format!(
+            "{}{}\n{} --> {}\n{}\n",
+            header,
+            self.index,
+            vtt_timestamp(segment.start_offset),
+            vtt_timestamp(segment.end_offset),
+            segment.text,
+        )
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn hms_millis(d: Duration) -> (i64, i64, i64, i64) {
+    let total = d.num_milliseconds().max(0);
+    (total / 3_600_000, total / 60_000 % 60, total / 1_000 % 60, total % 1_000)
+}
+
+fn srt_timestamp(d: Duration) -> String {
+    let (h, m, s, ms) = hms_millis(d);
+    format!("{h:02}:{m:02}:{s:02},{ms:03}")
+}
+
+fn vtt_timestamp(d: Duration) -> String {
+    let (h, m, s, ms) = hms_millis(d);
+    format!("{h:02}:{m:02}:{s:02}.{ms:03}")
+}
@@ -1,5 +1,4 @@
 use tokio::time::Duration;
-use std::process;
 use chrono::prelude::*;
 
 use windows::{
@@ -10,16 +9,100 @@ use log::{error,info};
 
 use anyhow::Result;
 
+mod event;
+use event::Event;
+mod format;
+use format::{Formatter, OutputFormat, Segment};
+mod service;
+
+use clap::Subcommand;
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+/// Options controlling a normal (interactive) capture run.
+#[derive(clap::Args, Debug, Clone)]
+struct RunArgs {
     /// Name of the file to output
     #[arg(short, long)]
-    file: String,
+    file: Option<String>,
 
     /// interval of minutes for one cycle
     #[arg(short, long, default_value_t = 3,value_parser=clap::value_parser!(u8).range(1..6))]
     interval: u8,
+
+    /// react the instant captions change via UI Automation instead of polling
+    #[arg(long)]
+    live: bool,
+
+    /// output format for the saved captions
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// seed the buffer from the tail of an existing --file so a restart continues cleanly
+    #[arg(long, overrides_with = "no_resume")]
+    resume: bool,
+
+    /// start from an empty buffer even when --file already exists
+    #[arg(long, overrides_with = "resume")]
+    no_resume: bool,
+}
+
+impl RunArgs {
+    /// Resume is on by default; `--no-resume` turns it off and `--resume` turns
+    /// it back on (the two override each other, last one wins).
+    fn resume_enabled(&self) -> bool {
+        self.resume || !self.no_resume
+    }
+}
+
+/// How many trailing caption lines to seed the buffer with on resume.
+const RESUME_LINES: usize = 8;
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Install, remove, or run the background Windows service.
+    Service {
+        #[command(subcommand)]
+        action: Option<ServiceAction>,
+    },
+    /// Follow the rotating service log file.
+    Tail,
+}
+
+#[derive(Subcommand, Debug)]
+enum ServiceAction {
+    /// Register the service with the SCM so it starts on boot.
+    Install,
+    /// Remove a previously installed service.
+    Uninstall,
+    /// Entry point invoked by the SCM; not meant to be run from a console.
+    Run,
+}
+
+/// UI Automation property-changed handler that forwards every new caption name
+/// onto the event bus, so the engine reacts the moment the text changes rather
+/// than on the next `Event::CaptionTick`.
+#[implement(IUIAutomationPropertyChangedEventHandler)]
+struct CaptionHandler {
+    writer: event::Writer,
+}
+
+impl IUIAutomationPropertyChangedEventHandler_Impl for CaptionHandler_Impl {
+    fn HandlePropertyChangedEvent(&self, sender: Ref<IUIAutomationElement>, _propertyid: UIA_PROPERTY_ID, _newvalue: &VARIANT) -> Result<()> {
+        if let Some(sender) = sender.as_ref() {
+            let name = unsafe { sender.CurrentName() }?;
+            self.writer.send(Event::CaptionChanged(name.to_string()));
+        }
+        Ok(())
+    }
 }
 
 struct Engine
@@ -28,24 +111,87 @@ struct Engine
         condition:  IUIAutomationCondition,
         prebuffer: String,
         sfilename:String,
+        live_element: Option<IUIAutomationElement>,
+        handler: Option<IUIAutomationPropertyChangedEventHandler>,
+        live_writer: Option<event::Writer>,
+        live_hwnd: isize,
+        formatter: Box<dyn Formatter>,
+        started: DateTime<Local>,
+        last_appeared: Option<DateTime<Local>>,
 }
 
 impl Drop for Engine {
     fn drop(&mut self) {
+        if let (Some(element),Some(handler)) = (&self.live_element,&self.handler) {
+            let _ = unsafe { self.automation.RemovePropertyChangedEventHandler(element, handler) };
+        }
         unsafe{CoUninitialize();}
     }
 }
 impl Engine {    
-    fn new(sfilename:&str)->Self{
-        unsafe { CoInitializeEx(None, COINIT_MULTITHREADED).ok().expect("Failed initial Winodws COM.");};        
+    fn new(sfilename:&str,format:OutputFormat)->Self{
+        unsafe { CoInitializeEx(None, COINIT_MULTITHREADED).ok().expect("Failed initial Winodws COM.");};
 
         let automation:IUIAutomation =  unsafe { CoCreateInstance(&CUIAutomation, None, CLSCTX_ALL).expect("Failed initial Winodws Accessibility API.") };
         let condition  = unsafe { automation.CreatePropertyCondition(UIA_AutomationIdPropertyId,  &VARIANT::from("CaptionsTextBlock")).unwrap()};
         Self {automation,condition,
             prebuffer:Default::default(),
             sfilename:sfilename.to_string(),
+            live_element:None,
+            handler:None,
+            live_writer:None,
+            live_hwnd:0,
+            formatter:format.formatter(),
+            started:Local::now(),
+            last_appeared:None,
         }
     }
+    /// Register a UI Automation handler on the `CaptionsTextBlock` element so
+    /// each name change is forwarded through `writer` as `Event::CaptionChanged`.
+    /// Returns the error if the element cannot be resolved, leaving capture to
+    /// the regular `Event::CaptionTick` polling (the LiveCaptions control is
+    /// sometimes recreated); `refresh_live_handler` re-resolves the element
+    /// after a window change.
+    fn register_live_handler(&mut self,writer:event::Writer)->Result<()> {
+        let window = unsafe { FindWindowW(w!("LiveCaptionsDesktopWindow"), None) };
+        let element = unsafe { self.automation.ElementFromHandle(window) }?;
+        let element = unsafe { element.FindFirst(TreeScope_Descendants, &self.condition) }?;
+        let handler:IUIAutomationPropertyChangedEventHandler = CaptionHandler{writer:writer.clone()}.into();
+        unsafe {
+            self.automation.AddPropertyChangedEventHandler(
+                &element,
+                TreeScope_Element,
+                None,
+                &handler,
+                &[UIA_NamePropertyId],
+            )?;
+        }
+        self.live_element = Some(element);
+        self.handler = Some(handler);
+        self.live_hwnd = window.0;
+        self.live_writer = Some(writer);
+        Ok(())
+    }
+    /// Tear down the currently registered handler, if any.
+    fn remove_live_handler(&mut self) {
+        if let (Some(element),Some(handler)) = (&self.live_element,&self.handler) {
+            let _ = unsafe { self.automation.RemovePropertyChangedEventHandler(element, handler) };
+        }
+        self.live_element = None;
+        self.handler = None;
+    }
+    /// Re-resolve the caption element and re-register the live handler when the
+    /// LiveCaptions window has been recreated (its `HWND` changed). No-op unless
+    /// live mode is active and the window handle actually changed.
+    fn refresh_live_handler(&mut self)->Result<()> {
+        let Some(writer) = self.live_writer.clone() else { return Ok(()) };
+        let window = unsafe { FindWindowW(w!("LiveCaptionsDesktopWindow"), None) };
+        if window.0 == self.live_hwnd {
+            return Ok(());
+        }
+        self.remove_live_handler();
+        self.register_live_handler(writer)
+    }
     fn get_livecaptions(&self) -> Result<String> {
         let window = unsafe { FindWindowW(w!("LiveCaptionsDesktopWindow"), None) };
         let element = unsafe { self.automation.ElementFromHandle(window) }?;
@@ -53,45 +199,58 @@ impl Engine {
         let text =unsafe { text.CurrentName()}?;
         Ok(text.to_string())
     }
-    fn save_current_captions(&mut self,current:&str,include_last_line:bool)->Result<()> 
+    fn save_current_captions(&mut self,current:&str,include_last_line:bool)->Result<()>
     {
         use std::fs::OpenOptions;
         use std::io::prelude::*;
-        let last_line = if !include_last_line {1} else {0};
 
-         //从current的所有行中，找到第一行不在prebuffer的行 x 
-        //将 行 x 到 current 倒数第2行，加入到prebuffer之后
         //最后一行ms livecaption会修正，所以不实时写入，在graceful_shutdown中，再写入。
-        let mut lines: Vec<&str> = current.lines().collect();
-        let mut first_new_line = None;
-    
-        // 找到第一个不在 prebuffer 中的行
-        for (i, line) in lines.iter().enumerate() {
-            if !self.prebuffer.contains(line) {
-                first_new_line = Some(i);
-                break;
-            }
+        let effective = if include_last_line {
+            current.to_string()
+        } else {
+            let mut lines: Vec<&str> = current.lines().collect();
+            lines.pop();
+            lines.join("\n")
+        };
+
+        let new_text = extract_new_lines(&self.prebuffer, &effective);
+        if new_text.is_empty() {
+            return Ok(());
+        }
+
+        self.prebuffer.push_str(&new_text);
+        if !self.prebuffer.ends_with('\n') {
+            self.prebuffer.push('\n');
         }
-        if let Some(start) = first_new_line {
-            // 将新行添加到 prebuffer 中
-            let mut file = OpenOptions::new()
+
+        let now = Local::now();
+        // First cue starts at program start; later cues start where the previous
+        // flush finalized, so every cue has a real, non-zero duration.
+        let appeared = self.last_appeared.unwrap_or(self.started);
+        let segment = Segment {
+            text: if new_text.ends_with('\n') { new_text } else { format!("{new_text}\n") },
+            ts: now,
+            start_offset: appeared - self.started,
+            end_offset: now - self.started,
+        };
+
+        let mut file = OpenOptions::new()
             .create(true)
             .append(true)
             .open(&self.sfilename)?;
-            
-            let local: DateTime<Local> = Local::now();
-            write!(file, "{}\n", local.format("[%Y-%m-%d][%H:%M:%S]"))?;
-            for line in lines.drain(start..lines.len() - last_line) {
-                self.prebuffer.push_str(line);
-                self.prebuffer.push('\n');
-
-                file.write_all(line.as_bytes())?;
-                file.write(b"\n")?;
+        file.write_all(self.formatter.format(&segment).as_bytes())?;
 
-            }
-        }                
+        self.last_appeared = Some(now);
         Ok(())
     }
+    /// Pre-fill `prebuffer` with already-logged lines so the first flush after
+    /// a restart only contains genuinely new text.
+    fn seed_prebuffer(&mut self,lines:&[String]) {
+        for line in lines {
+            self.prebuffer.push_str(line);
+            self.prebuffer.push('\n');
+        }
+    }
     fn graceful_shutdown(&mut self)->Result<()> {
         let text = self.get_livecaptions()?;
         self.save_current_captions(&text,true)?;
@@ -99,56 +258,269 @@ impl Engine {
     }
 }
 
-fn is_livecaptions_running()->bool{   
-    return unsafe{FindWindowW(w!("LiveCaptionsDesktopWindow"), None).0}!=0;
+/// Decide what part of `current` is genuinely new relative to `previous`.
+///
+/// Live Captions shows a sliding window of the last few lines, so `current`
+/// usually repeats some trailing lines of `previous`. We find the longest run
+/// of `current`'s leading lines that also appears as a contiguous block in
+/// `previous` and emit everything after it — this is robust to lines that
+/// legitimately repeat or to the window re-wrapping. The first un-matched
+/// current line is then checked with a character-level boundary match against
+/// the previous line the match stopped at, so a line extended in place
+/// (`"Line 3"` becoming `"Line 3 Line 3"`) only emits the appended tail even
+/// when earlier lines already overlapped.
+fn extract_new_lines(previous: &str, current: &str) -> String {
+    let prev_lines: Vec<&str> = previous.lines().collect();
+    let cur_lines: Vec<&str> = current.lines().collect();
+
+    // longest run of leading current lines matching a contiguous block of previous
+    let mut overlap = 0usize;
+    let mut best_i = 0usize;
+    for i in 0..prev_lines.len() {
+        let mut k = 0usize;
+        while i + k < prev_lines.len() && k < cur_lines.len() && prev_lines[i + k] == cur_lines[k] {
+            k += 1;
+        }
+        if k > overlap {
+            overlap = k;
+            best_i = i;
+        }
+    }
+
+    // The line the match stopped at may be the previous boundary line extended
+    // in place; trim its already-seen prefix. When there was no whole-line
+    // overlap at all, the boundary is previous's last line.
+    let prev_boundary = if overlap > 0 {
+        prev_lines.get(best_i + overlap).copied()
+    } else {
+        prev_lines.last().copied()
+    };
+    if let (Some(prev_boundary), Some(cur_boundary)) = (prev_boundary, cur_lines.get(overlap).copied()) {
+        let k = boundary_overlap(prev_boundary, cur_boundary);
+        if k > 0 && k < cur_boundary.len() {
+            let mut rest: Vec<&str> = cur_lines[overlap..].to_vec();
+            rest[0] = &cur_boundary[k..];
+            return join_lines(&rest);
+        }
+    }
+
+    if overlap > 0 {
+        return join_lines(&cur_lines[overlap..]);
+    }
+
+    current.to_string()
 }
 
+/// Join caption lines with a trailing newline, or return `""` when empty.
+fn join_lines(lines: &[&str]) -> String {
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
 
-#[tokio::main]
-async fn main(){
+/// Length of the longest suffix of `prev` that is also a prefix of `cur`.
+fn boundary_overlap(prev: &str, cur: &str) -> usize {
+    let max = prev.len().min(cur.len());
+    (1..=max)
+        .rev()
+        .find(|&k| {
+            prev.is_char_boundary(prev.len() - k)
+                && cur.is_char_boundary(k)
+                && prev[prev.len() - k..] == cur[..k]
+        })
+        .unwrap_or(0)
+}
 
-    env_logger::init();
-    let args = Args::parse();
-    info!("get-livecaptions running.");
+/// Read up to the last `n` caption lines of `path`, skipping timestamp
+/// headers. Seeks near the end rather than reading the whole file so a large
+/// log is cheap to resume from; returns empty if the file is missing.
+async fn read_tail_lines(path:&str,n:usize)->Vec<String> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
-    if !is_livecaptions_running()
+    let Ok(mut file) = tokio::fs::File::open(path).await else { return Vec::new() };
+    let Ok(meta) = file.metadata().await else { return Vec::new() };
+
+    let start = meta.len().saturating_sub(8 * 1024);
+    if file.seek(std::io::SeekFrom::Start(start)).await.is_err() {
+        return Vec::new();
+    }
+    let mut buf = Vec::new();
+    if file.read_to_end(&mut buf).await.is_err() {
+        return Vec::new();
+    }
+
+    let text = String::from_utf8_lossy(&buf);
+    let mut raw: Vec<&str> = text.lines().collect();
+    // seeking into the middle of the file can split a line, so drop the first
+    // (possibly partial) line unless we started at the very beginning.
+    if start > 0 && !raw.is_empty() {
+        raw.remove(0);
+    }
+    let lines: Vec<String> = raw
+        .into_iter()
+        .filter(|l| !l.is_empty() && !is_timestamp_line(l))
+        .map(|l| l.to_string())
+        .collect();
+    let skip = lines.len().saturating_sub(n);
+    lines[skip..].to_vec()
+}
+
+/// Whether a line is a `[date][time]` header written by the text formatter.
+fn is_timestamp_line(line:&str)->bool {
+    line.starts_with('[') && line.contains("][")
+}
+
+fn is_livecaptions_running()->bool{
+    return unsafe{FindWindowW(w!("LiveCaptionsDesktopWindow"), None).0}!=0;
+}
+
+#[cfg(test)]
+mod tests;
+
+
+/// Spawn the periodic input tasks (caption flush ticker, window-alive watcher)
+/// that push events onto the bus.
+fn spawn_timers(writer: &event::Writer, interval: u8) {
+    // caption flush ticker
     {
-        error!("livecaptions is not running. programe exiting.");
-        return;
+        let writer = writer.clone();
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(Duration::from_secs(interval as u64 * 60));
+            loop {
+                timer.tick().await;
+                writer.send(Event::CaptionTick);
+            }
+        });
+    }
+    // window-alive watcher
+    {
+        let writer = writer.clone();
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(Duration::from_secs(10));
+            loop {
+                timer.tick().await;
+                writer.send(Event::WindowCheck);
+            }
+        });
     }
-    let mut engine = Engine::new(&args.file);
+}
 
-    let mut windows_timer = tokio::time::interval(Duration::from_secs(10));
-    let mut writefile_timer = tokio::time::interval(Duration::from_secs(args.interval as u64 * 60));
+/// Drive the engine off a single event-bus reader until `Event::Shutdown` or a
+/// lost Live Captions window. `writer` is kept for registering the live handler.
+async fn drive_engine(run: &RunArgs, writer: event::Writer, mut reader: event::Reader) {
+    let file = run.file.as_deref().unwrap_or("livecaptions.txt");
+    let mut engine = Engine::new(file, run.format);
 
+    if run.resume_enabled() {
+        // Only the text format writes bare caption lines back out; the other
+        // formats interleave JSON objects / cue numbers / timestamps that would
+        // never match the live caption window, so seeding from them would defeat
+        // overlap detection and re-emit everything. Warn rather than mis-seed.
+        if run.format == OutputFormat::Text {
+            let seed = read_tail_lines(file, RESUME_LINES).await;
+            if !seed.is_empty() {
+                info!("resuming from {} existing caption line(s).", seed.len());
+                engine.seed_prebuffer(&seed);
+            }
+        } else {
+            error!("--resume is only supported for --format text; starting with an empty buffer.");
+        }
+    }
 
-    let ctrl_c = tokio::signal::ctrl_c();
-    tokio::pin!(ctrl_c);
+    if run.live {
+        if let Err(e) = engine.register_live_handler(writer.clone()) {
+            error!("failed to register live caption handler, falling back to polling: {e}");
+        } else {
+            info!("live caption mode enabled.");
+        }
+    }
 
-    println!("get-livecaptions is running now, and save content into '{}', every {} min. ctrl-c for exit.",args.file, args.interval);
-    loop{
-        tokio::select!{
-            _ = windows_timer.tick() => {
+    while let Some(ev) = reader.recv().await {
+        match ev {
+            Event::WindowCheck => {
                 log::info!("running checking, every 10s.");
                 if !is_livecaptions_running()
                 {
                     println!("livecaptions is not running. programe exiting.");
                     let _ = engine.graceful_shutdown();
-                    process::exit(0);
+                    break;
+                }
+                // the LiveCaptions control is sometimes recreated; re-register
+                // the live handler against the new window handle if so.
+                if let Err(e) = engine.refresh_live_handler() {
+                    error!("failed to re-register live caption handler: {e}");
                 }
             },
-            _ = writefile_timer.tick() => {
-                log::info!("save content into file, every {} min.",args.interval);
+            Event::CaptionTick => {
+                log::info!("save content into file, every {} min.",run.interval);
                 let text = engine.get_livecaptions();
                 if let Ok(text) = text {
                     engine.save_current_captions(&text,false).expect("save file failed.");
-                }                                            
+                }
             },
-            _ = &mut ctrl_c => {
+            Event::CaptionChanged(text) => {
+                engine.save_current_captions(&text,false).expect("save file failed.");
+            },
+            Event::Shutdown => {
                 let _ = engine.graceful_shutdown();
-                process::exit(0);
+                break;
             }
-        };
-    };
+        }
+    }
+}
+
+#[tokio::main]
+async fn main(){
+
+    let args = Args::parse();
+
+    match args.command {
+        Some(Command::Service { action }) => {
+            let result = match action.unwrap_or(ServiceAction::Run) {
+                ServiceAction::Install => service::install(),
+                ServiceAction::Uninstall => service::uninstall(),
+                ServiceAction::Run => service::run(args.run),
+            };
+            if let Err(e) = result {
+                error!("service error: {e}");
+            }
+            return;
+        }
+        Some(Command::Tail) => {
+            if let Err(e) = service::tail() {
+                error!("tail error: {e}");
+            }
+            return;
+        }
+        None => {}
+    }
+
+    env_logger::init();
+    info!("get-livecaptions running.");
+
+    if !is_livecaptions_running()
+    {
+        error!("livecaptions is not running. programe exiting.");
+        return;
+    }
+
+    let (writer, reader) = event::channel();
+    spawn_timers(&writer, args.run.interval);
+    // ctrl-c signal handler
+    {
+        let writer = writer.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                writer.send(Event::Shutdown);
+            }
+        });
+    }
+
+    let file = args.run.file.as_deref().unwrap_or("livecaptions.txt");
+    println!("get-livecaptions is running now, and save content into '{}', every {} min. ctrl-c for exit.",file, args.run.interval);
+    drive_engine(&args.run, writer, reader).await;
 }
 
@@ -0,0 +1,224 @@
+//! Background Windows service integration plus a dependency-light log tail.
+//!
+//! Running as a service lets caption capture survive logout of the interactive
+//! shell. Engine logs are routed to a rotating file under `%LOCALAPPDATA%` so
+//! the `tail` subcommand can follow capture activity without a console
+//! attached.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+use crate::{drive_engine, event, spawn_timers, RunArgs};
+
+const SERVICE_NAME: &str = "get-livecaptions";
+const LOG_ROTATE_BYTES: u64 = 1 << 20; // 1 MiB
+
+static RUN_ARGS: OnceLock<RunArgs> = OnceLock::new();
+static SHUTDOWN: OnceLock<event::Writer> = OnceLock::new();
+
+fn app_dir() -> Result<PathBuf> {
+    let base = std::env::var_os("LOCALAPPDATA").ok_or_else(|| anyhow!("LOCALAPPDATA is not set"))?;
+    let mut dir = PathBuf::from(base);
+    dir.push("get-livecaptions");
+    Ok(dir)
+}
+
+/// Location of the rotating service log.
+pub fn log_path() -> Result<PathBuf> {
+    let mut p = app_dir()?;
+    p.push("get-livecaptions.log");
+    Ok(p)
+}
+
+/// A minimal `log::Log` that appends to the service log and rotates it to
+/// `*.log.1` once it grows past [`LOG_ROTATE_BYTES`].
+struct FileLogger {
+    path: PathBuf,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let _ = self.append(record);
+        }
+    }
+    fn flush(&self) {}
+}
+
+impl FileLogger {
+    fn append(&self, record: &Record) -> io::Result<()> {
+        if let Ok(meta) = fs::metadata(&self.path) {
+            if meta.len() >= LOG_ROTATE_BYTES {
+                let _ = fs::rename(&self.path, self.path.with_extension("log.1"));
+            }
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "[{}] {}", record.level(), record.args())
+    }
+}
+
+fn init_file_logger() -> Result<()> {
+    let path = log_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    log::set_boxed_logger(Box::new(FileLogger { path }))
+        .map_err(|e| anyhow!("failed to set logger: {e}"))?;
+    log::set_max_level(LevelFilter::Info);
+    Ok(())
+}
+
+/// Register the service with the SCM, pointing it at `<exe> service run` so the
+/// SCM relaunches us in the dispatcher entry point on boot.
+pub fn install() -> Result<()> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from("Get Live Captions"),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: vec![OsString::from("service"), OsString::from("run")],
+        dependencies: vec![],
+        account_name: None,
+        account_password: None,
+    };
+    manager.create_service(&info, ServiceAccess::QUERY_STATUS)?;
+    println!("installed service '{SERVICE_NAME}'.");
+    Ok(())
+}
+
+/// Remove a previously installed service.
+pub fn uninstall() -> Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(SERVICE_NAME, ServiceAccess::DELETE)?;
+    service.delete()?;
+    println!("uninstalled service '{SERVICE_NAME}'.");
+    Ok(())
+}
+
+/// Hand the run options to the service entry point and start the control
+/// dispatcher. Invoked by the SCM (via `service run`); returns once the
+/// service stops.
+pub fn run(run: RunArgs) -> Result<()> {
+    RUN_ARGS.set(run).map_err(|_| anyhow!("service already started"))?;
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)?;
+    Ok(())
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn service_main(_args: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        // once detached there is no console; best-effort to the log file
+        log::error!("service failed: {e}");
+    }
+}
+
+fn run_service() -> Result<()> {
+    init_file_logger()?;
+
+    let run = RUN_ARGS.get().cloned().unwrap_or_else(|| RunArgs {
+        file: None,
+        interval: 3,
+        live: false,
+        format: crate::format::OutputFormat::Text,
+        resume: false,
+        no_resume: false,
+    });
+
+    let (writer, reader) = event::channel();
+    let _ = SHUTDOWN.set(writer.clone());
+
+    // SERVICE_CONTROL_STOP drives the same graceful_shutdown path as ctrl-c.
+    let event_handler = move |control| -> ServiceControlHandlerResult {
+        match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                if let Some(w) = SHUTDOWN.get() {
+                    w.send(event::Event::Shutdown);
+                }
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        }
+    };
+    let status_handle = service_control_handler::register(SERVICE_NAME, event_handler)?;
+
+    let running = ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: ServiceState::Running,
+        controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    };
+    status_handle.set_service_status(running.clone())?;
+
+    // the COM-bound engine must stay on a single thread
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    rt.block_on(async {
+        spawn_timers(&writer, run.interval);
+        drive_engine(&run, writer.clone(), reader).await;
+    });
+
+    status_handle.set_service_status(ServiceStatus {
+        current_state: ServiceState::Stopped,
+        ..running
+    })?;
+    Ok(())
+}
+
+/// Follow the service log by polling its size, mirroring the deliberately
+/// dependency-light approach used elsewhere. Restarts from the top when the
+/// file is rotated out from under us.
+pub fn tail() -> Result<()> {
+    let path = log_path()?;
+    println!("following {} (ctrl-c to stop)", path.display());
+
+    let mut pos: u64 = 0;
+    loop {
+        if let Ok(meta) = fs::metadata(&path) {
+            let len = meta.len();
+            if len < pos {
+                pos = 0; // rotated or truncated
+            }
+            if len > pos {
+                let mut file = File::open(&path)?;
+                file.seek(SeekFrom::Start(pos))?;
+                let mut buf = String::new();
+                file.read_to_string(&mut buf)?;
+                print!("{buf}");
+                io::stdout().flush()?;
+                pos = len;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
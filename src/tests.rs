@@ -51,9 +51,9 @@ fn test_extract_new_lines_partial_overlap2() {
     let current = "Line 2\nLine 3 Line 3\nLine 4\nLine 5";
 
     let result = extract_new_lines(previous, current);
-    // Finds "Line 2" as overlap (1 line match), returns content after it
-    // "Line 3" doesn't match "Line 3 Line 3", so match stops at 1 line
-    assert_eq!(result, "Line 3 Line 3\nLine 4\nLine 5\n");
+    // "Line 2" overlaps (1 line match); the boundary line "Line 3" was extended
+    // in place to "Line 3 Line 3", so only the appended tail is emitted.
+    assert_eq!(result, " Line 3\nLine 4\nLine 5\n");
 }
 
 #[test]
@@ -65,6 +65,35 @@ fn test_extract_new_lines_empty_previous_lines() {
     assert!(result.contains("New content here"));
 }
 
+#[test]
+fn test_extract_new_lines_repeated_identical_lines() {
+    let previous = "Hello\nHello";
+    let current = "Hello\nHello\nHello";
+
+    let result = extract_new_lines(previous, current);
+    // the genuinely new repeat is emitted, not mistaken for an existing line
+    assert_eq!(result, "Hello\n");
+}
+
+#[test]
+fn test_extract_new_lines_repeated_no_new() {
+    let previous = "Yes\nYes";
+    let current = "Yes\nYes";
+
+    let result = extract_new_lines(previous, current);
+    assert_eq!(result, "");
+}
+
+#[test]
+fn test_extract_new_lines_line_extended_in_place() {
+    let previous = "Line 3";
+    let current = "Line 3 Line 3";
+
+    // no whole-line overlap, so only the appended tail is emitted
+    let result = extract_new_lines(previous, current);
+    assert_eq!(result, " Line 3\n");
+}
+
 #[test]
 fn test_extract_new_lines_multiple_new_lines() {
     let previous = "First line";